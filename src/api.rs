@@ -0,0 +1,75 @@
+//! Admin HTTP API for inspecting and adopting devices at runtime.
+//!
+//! Mounted under `/api` alongside the `/metrics` route: `GET /api/devices`
+//! lists everything the store knows, `PUT /api/devices/{address}` names
+//! (or renames) one, and `POST /api/reload` re-reads the YAML config and
+//! merges in new device definitions and reloaded thermostat setpoints
+//! without touching accumulated readings or actuator state.
+
+use std::{
+    fs::File,
+    io::Read,
+    sync::{Arc, Mutex},
+};
+
+use enocean::packet::Address;
+use serde::Deserialize;
+use warp::{http::StatusCode, Filter};
+use yaml_rust::YamlLoader;
+
+use crate::{Result, TemperatureStore};
+
+#[derive(Debug, Deserialize)]
+struct SetName {
+    name: String,
+}
+
+fn reply(status: StatusCode, body: serde_json::Value) -> Box<dyn warp::Reply> {
+    Box::new(warp::reply::with_status(warp::reply::json(&body), status))
+}
+
+pub fn routes(
+    store: Arc<Mutex<TemperatureStore>>,
+    config_path: &'static str,
+) -> warp::filters::BoxedFilter<(Box<dyn warp::Reply>,)> {
+    let list_store = store.clone();
+    let list = warp::path!("api" / "devices")
+        .and(warp::get())
+        .map(move || reply(StatusCode::OK, serde_json::json!(list_store.lock().unwrap().snapshot())));
+
+    let rename_store = store.clone();
+    let rename = warp::path!("api" / "devices" / Address)
+        .and(warp::put())
+        .and(warp::body::json())
+        .map(move |address: Address, body: SetName| {
+            rename_store.lock().unwrap().set_name(address, body.name);
+            reply(StatusCode::OK, serde_json::json!({"status": "ok"}))
+        });
+
+    let reload_store = store.clone();
+    let reload = warp::path!("api" / "reload")
+        .and(warp::post())
+        .map(move || match reload_devices(&reload_store, config_path) {
+            Ok(()) => reply(StatusCode::OK, serde_json::json!({"status": "ok"})),
+            Err(e) => reply(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({"error": e.to_string()})),
+        });
+
+    list.or(rename).unify().or(reload).unify().boxed()
+}
+
+fn reload_devices(store: &Arc<Mutex<TemperatureStore>>, config_path: &str) -> Result<()> {
+    let mut config_file = String::new();
+    File::open(config_path)?.read_to_string(&mut config_file)?;
+    let config = YamlLoader::load_from_str(&config_file)?.into_iter().next().ok_or("empty config")?;
+    let devices = config["devices"].as_hash().ok_or("devices is not a table")?;
+
+    let mut store = store.lock().unwrap();
+    store.merge_devices(devices)?;
+    if let Some(thermostats) = config["thermostats"].as_hash() {
+        store.merge_thermostats(thermostats)?;
+    }
+    drop(store);
+
+    eprintln!("Reloaded device and thermostat definitions from {config_path}.");
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Bang-bang thermostat control with hysteresis.
+//!
+//! Each configured thermostat watches the readings for one sensor
+//! address and drives an EnOcean D2-01-xx switch actuator. Evaluation
+//! happens synchronously whenever a fresh temperature reading is
+//! inserted into the store; the resulting command is handed to the
+//! serial driver thread over a channel, since that thread is the sole
+//! owner of the `Port`.
+
+use std::time::{Duration, Instant};
+
+use enocean::{enocean::Rorg, packet::{Address, Packet, RadioErp1}};
+
+#[derive(Debug, Clone)]
+pub struct ThermostatConfig {
+    pub actuator: Address,
+    pub setpoint: f64,
+    pub hysteresis: f64,
+    pub min_dwell: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActuatorState {
+    On,
+    Off,
+}
+
+#[derive(Debug)]
+pub struct Thermostat {
+    config: ThermostatConfig,
+    state: ActuatorState,
+    since: Instant,
+    on_time: Duration,
+    total_time: Duration,
+}
+
+impl Thermostat {
+    pub fn new(config: ThermostatConfig) -> Self {
+        Self {
+            config,
+            state: ActuatorState::Off,
+            since: Instant::now(),
+            on_time: Duration::ZERO,
+            total_time: Duration::ZERO,
+        }
+    }
+
+    pub fn setpoint(&self) -> f64 {
+        self.config.setpoint
+    }
+
+    pub fn state(&self) -> ActuatorState {
+        self.state
+    }
+
+    pub fn actuator(&self) -> Address {
+        self.config.actuator
+    }
+
+    /// Applies a reloaded configuration (setpoint, hysteresis, actuator,
+    /// min dwell) without resetting the actuator state or duty-cycle
+    /// bookkeeping, so a config reload doesn't cause a spurious flip.
+    pub fn reconfigure(&mut self, config: ThermostatConfig) {
+        self.config = config;
+    }
+
+    /// Fraction of this thermostat's lifetime spent with the actuator on.
+    ///
+    /// `on_time`/`total_time` only get credited for past spans, up to
+    /// the last flip; the current in-progress span (from `self.since` to
+    /// `now`) is added in here rather than waiting for the next flip to
+    /// commit it, so a thermostat that settles into one state for hours
+    /// still reports a live duty cycle instead of a stale one.
+    pub fn duty_cycle(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.since);
+        let total_time = self.total_time + elapsed;
+        if total_time.is_zero() {
+            return 0f64;
+        }
+        let on_time = if self.state == ActuatorState::On {
+            self.on_time + elapsed
+        } else {
+            self.on_time
+        };
+        on_time.as_secs_f64() / total_time.as_secs_f64()
+    }
+
+    /// Feed a fresh temperature reading into the controller.
+    ///
+    /// Returns the new actuator state when the controller decides to
+    /// flip it, honoring both the hysteresis band and the minimum dwell
+    /// time since the last flip.
+    pub fn evaluate(&mut self, temperature: f64, now: Instant) -> Option<ActuatorState> {
+        let half_band = self.config.hysteresis / 2f64;
+        let desired = if temperature > self.config.setpoint + half_band {
+            ActuatorState::On
+        } else if temperature < self.config.setpoint - half_band {
+            ActuatorState::Off
+        } else {
+            self.state
+        };
+
+        if desired == self.state {
+            return None;
+        }
+
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed < self.config.min_dwell {
+            return None;
+        }
+
+        self.total_time += elapsed;
+        if self.state == ActuatorState::On {
+            self.on_time += elapsed;
+        }
+        self.since = now;
+        self.state = desired;
+
+        Some(desired)
+    }
+}
+
+/// Encode a D2-01-xx "actuator set output" command as an outbound ERP1
+/// telegram, addressed to `actuator`. `sender_id` is left as the zero
+/// address here; this function has no way to know the transceiver's own
+/// base ID, only the serial driver thread (which owns the `Port`) does,
+/// so it overwrites `sender_id` with the dongle's real base ID before
+/// the telegram is written out.
+pub fn actuator_command(actuator: Address, state: ActuatorState) -> Packet {
+    let output = match state {
+        ActuatorState::On => 0x64,
+        ActuatorState::Off => 0x00,
+    };
+    Packet::RadioErp1(RadioErp1 {
+        choice: Rorg::Vld,
+        sender_id: Address::default(),
+        destination_id: actuator,
+        user_data: vec![0x01, output, 0x00, 0x00],
+        status: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ThermostatConfig {
+        ThermostatConfig {
+            actuator: Address::default(),
+            setpoint: 20f64,
+            hysteresis: 1f64,
+            min_dwell: Duration::from_secs(0),
+        }
+    }
+
+    #[test]
+    fn duty_cycle_counts_the_in_progress_span_without_a_flip() {
+        let t0 = Instant::now();
+        let mut thermostat = Thermostat::new(config());
+
+        // Flip on right away, then let a lot of time pass with no further
+        // flip (the room never reaches the off-threshold).
+        assert_eq!(thermostat.evaluate(25f64, t0), Some(ActuatorState::On));
+        assert_eq!(thermostat.duty_cycle(t0), 0f64);
+
+        let later = t0 + Duration::from_secs(3600);
+        assert_eq!(thermostat.duty_cycle(later), 1f64);
+    }
+
+    #[test]
+    fn duty_cycle_blends_committed_and_in_progress_spans() {
+        let t0 = Instant::now();
+        let mut thermostat = Thermostat::new(config());
+
+        // On for 10s, then off; duty cycle should reflect that committed
+        // span plus however long we've been off since.
+        assert_eq!(thermostat.evaluate(25f64, t0), Some(ActuatorState::On));
+        let t1 = t0 + Duration::from_secs(10);
+        assert_eq!(thermostat.evaluate(15f64, t1), Some(ActuatorState::Off));
+
+        let t2 = t1 + Duration::from_secs(10);
+        assert_eq!(thermostat.duty_cycle(t2), 0.5f64);
+    }
+}
@@ -0,0 +1,170 @@
+//! EnOcean Equipment Profile (EEP) decoding for 4BS (A5-xx-xx) telegrams.
+//!
+//! Only the subset of profiles this exporter knows how to turn into
+//! measurements is implemented here. `Eep::from_str` rejects any other
+//! profile, so a device configured with one fails the config load. A5-02
+//! types outside the supported ranges parse fine, but `decode` returns
+//! `None` for them so the caller can log and skip the telegram instead of
+//! silently misreading it as a plain A5-02-05 sensor.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A profile a configured device is expected to speak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eep {
+    /// A5-02-xx: single-channel temperature sensors. `xx` is the EEP
+    /// "type" byte, which selects the sensor's documented range.
+    A502(u8),
+    /// A5-04-01: temperature (0..40°C) + humidity (0..100%).
+    A50401,
+    /// A5-04-02: temperature (-20..60°C) + humidity (0..100%).
+    A50402,
+}
+
+impl FromStr for Eep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.splitn(3, '-').collect();
+        let [rorg, func, ty] = parts[..] else {
+            return Err(format!("malformed EEP {s:?}, expected RORG-FUNC-TYPE"));
+        };
+        match (rorg, func, ty) {
+            ("A5", "02", ty) => {
+                let ty = u8::from_str_radix(ty, 16).map_err(|e| format!("bad EEP type {ty:?}: {e}"))?;
+                Ok(Eep::A502(ty))
+            }
+            ("A5", "04", "01") => Ok(Eep::A50401),
+            ("A5", "04", "02") => Ok(Eep::A50402),
+            _ => Err(format!("unsupported EEP {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for Eep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Eep::A502(ty) => write!(f, "A5-02-{ty:02X}"),
+            Eep::A50401 => write!(f, "A5-04-01"),
+            Eep::A50402 => write!(f, "A5-04-02"),
+        }
+    }
+}
+
+/// A single named measurement decoded from a 4BS telegram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Measurement {
+    TemperatureCelsius(f64),
+    HumidityPercent(f64),
+}
+
+impl Measurement {
+    /// Short, stable name used as the store's key for this kind of reading.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Measurement::TemperatureCelsius(_) => "temperature",
+            Measurement::HumidityPercent(_) => "humidity",
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        match self {
+            Measurement::TemperatureCelsius(v) | Measurement::HumidityPercent(v) => *v,
+        }
+    }
+}
+
+/// A linear 8-bit scale over a documented range, as used by every A5-02
+/// and A5-04 data byte. `descending` is for the profiles where the raw
+/// byte runs from the high end of the range to the low end.
+#[derive(Debug, Clone, Copy)]
+struct Scale {
+    min: f64,
+    max: f64,
+    descending: bool,
+}
+
+impl Scale {
+    fn apply(&self, raw: u8) -> f64 {
+        let frac = raw as f64 / 255f64;
+        if self.descending {
+            self.max - frac * (self.max - self.min)
+        } else {
+            self.min + frac * (self.max - self.min)
+        }
+    }
+}
+
+/// Temperature range for an A5-02-xx profile, per the EEP spec's table
+/// of 8-bit temperature sensors. Only the plain 8-bit profiles are
+/// covered; the 10-bit and reversed-scale variants (A5-02-1B and up)
+/// aren't decoded here and fall through to `None`.
+fn a502_range(ty: u8) -> Option<Scale> {
+    match ty {
+        0x01..=0x0B => {
+            let min = -50f64 + 10f64 * ty as f64;
+            Some(Scale { min, max: min + 40f64, descending: true })
+        }
+        0x10..=0x1A => {
+            let min = -60f64 + 10f64 * (ty - 0x10) as f64;
+            Some(Scale { min, max: min + 80f64, descending: true })
+        }
+        _ => None,
+    }
+}
+
+/// Decode the DB3..DB0 data bytes of a 4BS telegram according to `eep`.
+///
+/// Returns `None` when the profile is recognised but this telegram
+/// doesn't carry data this code knows how to read (e.g. a 10-bit A5-02
+/// variant); callers should log and skip rather than guess.
+pub fn decode(eep: Eep, user_data: &[u8]) -> Option<Vec<Measurement>> {
+    match eep {
+        Eep::A502(ty) => {
+            let scale = a502_range(ty)?;
+            let db2 = *user_data.get(2)?;
+            Some(vec![Measurement::TemperatureCelsius(scale.apply(db2))])
+        }
+        Eep::A50401 => {
+            let temp = Scale { min: 0f64, max: 40f64, descending: false }.apply(*user_data.get(2)?);
+            let humidity = Scale { min: 0f64, max: 100f64, descending: false }.apply(*user_data.get(1)?);
+            Some(vec![
+                Measurement::TemperatureCelsius(temp),
+                Measurement::HumidityPercent(humidity),
+            ])
+        }
+        Eep::A50402 => {
+            let temp = Scale { min: -20f64, max: 60f64, descending: false }.apply(*user_data.get(2)?);
+            let humidity = Scale { min: 0f64, max: 100f64, descending: false }.apply(*user_data.get(1)?);
+            Some(vec![
+                Measurement::TemperatureCelsius(temp),
+                Measurement::HumidityPercent(humidity),
+            ])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a502_05_is_descending_like_the_old_hard_coded_formula() {
+        // A5-02-05 is 0..40°C: raw 0x00 is the top of the range, raw
+        // 0xFF is the bottom, matching what `decode_temperature` used
+        // to compute before profile dispatch was introduced.
+        let eep = Eep::A502(0x05);
+        let measurements = decode(eep, &[0, 0, 0x00, 0]).unwrap();
+        assert_eq!(measurements, vec![Measurement::TemperatureCelsius(40.0)]);
+
+        let measurements = decode(eep, &[0, 0, 0xFF, 0]).unwrap();
+        assert_eq!(measurements, vec![Measurement::TemperatureCelsius(0.0)]);
+    }
+
+    #[test]
+    fn a504_01_temperature_is_ascending() {
+        let measurements = decode(Eep::A50401, &[0, 0, 0xFF, 0]).unwrap();
+        assert_eq!(measurements[0], Measurement::TemperatureCelsius(40.0));
+    }
+}
@@ -1,66 +1,310 @@
+mod api;
+mod control;
+mod eep;
+
 use std::{
     collections::HashMap,
-    fs::File, io::Read,
-    sync::{Arc, Mutex},
-    time::{SystemTime, UNIX_EPOCH},
+    fs::File, io::{Read, Write},
+    sync::{atomic::{AtomicBool, AtomicU64, Ordering}, mpsc, Arc, Mutex},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     net::SocketAddr};
 use enocean::{packet::{Address, Packet}, port::Port, enocean::Rorg};
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{family::Family, gauge::Gauge, info::Info},
+    registry::Registry,
+};
+use serde::Serialize;
 use warp::Filter;
 use yaml_rust::{Yaml, YamlLoader};
 use tokio::runtime::Handle;
 
+use control::{ActuatorState, Thermostat, ThermostatConfig};
+use eep::{Eep, Measurement};
+
+/// Labels attached to every per-device gauge.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct DeviceLabels {
+    address: String,
+    name: String,
+}
 
 type DeviceName = String;
-type Temperature = f64;
 type Timestamp = SystemTime;
 
-#[derive(Debug,Default)]
-struct TemperatureStore {
-    devices: HashMap<Address, (Option<DeviceName>, Option<(Temperature, Timestamp)>)>,
+/// Everything the store knows about one configured or discovered device.
+#[derive(Debug, Default)]
+struct Device {
+    name: Option<DeviceName>,
+    eep: Option<Eep>,
+    /// Latest value per measurement name (e.g. "temperature", "humidity").
+    readings: HashMap<&'static str, (f64, Timestamp)>,
 }
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+/// A device's name, EEP, and latest readings, as exposed by the admin API.
+#[derive(Debug, Serialize)]
+pub(crate) struct DeviceSnapshot {
+    address: String,
+    name: Option<DeviceName>,
+    eep: Option<String>,
+    readings: HashMap<&'static str, f64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct TemperatureStore {
+    devices: HashMap<Address, Device>,
+    /// Readings older than this are treated as absent rather than stale.
+    /// `None` means readings never expire.
+    max_age: Option<Duration>,
+    /// Thermostats, keyed by the sensor address they watch.
+    thermostats: HashMap<Address, Thermostat>,
+    /// Where actuator commands go to reach the serial driver thread.
+    /// `None` when no thermostats are configured.
+    commands: Option<mpsc::Sender<Packet>>,
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 impl TemperatureStore {
 
-    pub fn with_devices(config_devices: &yaml_rust::yaml::Hash) -> Result<Self> {
+    pub fn with_devices(config_devices: &yaml_rust::yaml::Hash, max_age: Option<Duration>) -> Result<Self> {
         let mut devices = HashMap::new();
-        for (address, name) in config_devices.iter() {
-            let name = name.as_str().ok_or("device name was not string")?.to_owned();            
-            let address = address.as_str().ok_or("device address was not a string")?.parse()?;
-            devices.insert(address, (Some(name), None));
+        for (address, fields) in config_devices.iter() {
+            let address: Address = address.as_str().ok_or("device address was not a string")?.parse()?;
+            let fields = fields.as_hash().ok_or("device entry was not a table")?;
+
+            let name = fields.get(&Yaml::String("name".into()))
+                .map(|y| y.as_str().ok_or("device name was not a string"))
+                .transpose()?
+                .map(str::to_owned);
+
+            let eep = fields.get(&Yaml::String("eep".into()))
+                .map(|y| y.as_str().ok_or("device eep was not a string"))
+                .transpose()?
+                .map(Eep::from_str)
+                .transpose()?;
+
+            devices.insert(address, Device { name, eep, readings: HashMap::new() });
+        }
+        Ok(Self { devices, max_age, thermostats: HashMap::new(), commands: None })
+    }
+
+    /// Adds thermostats parsed from the YAML `thermostats` table, wiring
+    /// them to the serial driver thread through `commands`.
+    pub fn with_thermostats(mut self, config_thermostats: &yaml_rust::yaml::Hash, commands: mpsc::Sender<Packet>) -> Result<Self> {
+        let mut thermostats = HashMap::new();
+        for (sensor, fields) in config_thermostats.iter() {
+            let (sensor, config) = parse_thermostat(sensor, fields)?;
+            thermostats.insert(sensor, Thermostat::new(config));
         }
-        Ok(Self { devices })
+        self.thermostats = thermostats;
+        self.commands = Some(commands);
+        Ok(self)
     }
 
-    pub fn insert(&mut self, address: Address, temperature: Temperature, timestamp: SystemTime) {
-        self.devices.entry(address)
-            .or_insert((None,None))
-            .1.replace((temperature, timestamp));
+    /// Applies reloaded setpoints/hysteresis/actuator/min-dwell from the
+    /// YAML `thermostats` table to the thermostats already running,
+    /// preserving their actuator state and duty-cycle bookkeeping. A
+    /// sensor that wasn't configured as a thermostat at startup isn't
+    /// adopted this way; it's logged and skipped.
+    pub fn merge_thermostats(&mut self, config_thermostats: &yaml_rust::yaml::Hash) -> Result<()> {
+        for (sensor, fields) in config_thermostats.iter() {
+            let (sensor, config) = parse_thermostat(sensor, fields)?;
+            match self.thermostats.get_mut(&sensor) {
+                Some(thermostat) => thermostat.reconfigure(config),
+                None => eprintln!("Thermostat for {sensor}: not configured at startup, restart to add it"),
+            }
+        }
+        Ok(())
     }
 
-    pub fn scrape(&mut self) -> String {
-        let mut scrape = format!("# HELP enocean_temperature_celsius Temperature reported by an EnOcean sensor, in Â°C\n");
-        scrape += &format!("# TYPE enocean_temperature_celsius gauge\n");
-
-        for (address, (name, point)) in self.devices.iter() {
-            if let Some((temp, time)) = point {
-                let time = time.duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis();
-                let address = address.to_string();
-                scrape += &
-                    if let Some(name) = name {
-                        format!("enocean_temperature_celsius{{address=\"{address}\", name=\"{name}\"}} {temp} {time}\n")
-                    } else {
-                        format!("enocean_temperature_celsius{{address=\"{address}\"}} {temp} {time}\n")
-                    }
+    /// Whether a reading taken at `timestamp` still counts as live.
+    fn is_fresh(&self, now: SystemTime, timestamp: Timestamp) -> bool {
+        match self.max_age {
+            Some(max_age) => now.duration_since(timestamp).map(|age| age <= max_age).unwrap_or(true),
+            None => true,
+        }
+    }
+
+    pub fn insert(&mut self, address: Address, measurements: Vec<Measurement>, timestamp: Timestamp) {
+        let device = self.devices.entry(address).or_default();
+        for measurement in &measurements {
+            device.readings.insert(measurement.name(), (measurement.value(), timestamp));
+        }
+
+        if let Some(Measurement::TemperatureCelsius(temperature)) =
+            measurements.into_iter().find(|m| m.name() == "temperature")
+        {
+            self.drive_thermostat(address, temperature);
+        }
+    }
+
+    /// Adds or updates devices from a YAML `devices` table without
+    /// disturbing the readings already accumulated for existing ones.
+    pub fn merge_devices(&mut self, config_devices: &yaml_rust::yaml::Hash) -> Result<()> {
+        for (address, fields) in config_devices.iter() {
+            let address: Address = address.as_str().ok_or("device address was not a string")?.parse()?;
+            let fields = fields.as_hash().ok_or("device entry was not a table")?;
+
+            let name = fields.get(&Yaml::String("name".into()))
+                .map(|y| y.as_str().ok_or("device name was not a string"))
+                .transpose()?
+                .map(str::to_owned);
+
+            let eep = fields.get(&Yaml::String("eep".into()))
+                .map(|y| y.as_str().ok_or("device eep was not a string"))
+                .transpose()?
+                .map(Eep::from_str)
+                .transpose()?;
+
+            let device = self.devices.entry(address).or_default();
+            if name.is_some() {
+                device.name = name;
+            }
+            if eep.is_some() {
+                device.eep = eep;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets or changes a device's friendly name, adopting it if it
+    /// hasn't been configured or seen before.
+    pub fn set_name(&mut self, address: Address, name: DeviceName) {
+        self.devices.entry(address).or_default().name = Some(name);
+    }
+
+    pub fn snapshot(&self) -> Vec<DeviceSnapshot> {
+        self.devices.iter().map(|(address, device)| DeviceSnapshot {
+            address: address.to_string(),
+            name: device.name.clone(),
+            eep: device.eep.map(|eep| eep.to_string()),
+            readings: device.readings.iter().map(|(name, (value, _))| (*name, *value)).collect(),
+        }).collect()
+    }
+
+    fn drive_thermostat(&mut self, sensor: Address, temperature: f64) {
+        let Some(thermostat) = self.thermostats.get_mut(&sensor) else { return };
+        let Some(state) = thermostat.evaluate(temperature, Instant::now()) else { return };
+        let actuator = thermostat.actuator();
+        if let Some(commands) = &self.commands {
+            if commands.send(control::actuator_command(actuator, state)).is_err() {
+                eprintln!("Thermostat for {sensor}: driver thread is gone, dropping command");
+            }
+        }
+    }
+
+    pub fn scrape(&self) -> String {
+        let mut registry = Registry::default();
+
+        let temperature = Family::<DeviceLabels, Gauge<f64, AtomicU64>>::default();
+        let humidity = Family::<DeviceLabels, Gauge<f64, AtomicU64>>::default();
+        let last_seen = Family::<DeviceLabels, Gauge<f64, AtomicU64>>::default();
+        registry.register("enocean_temperature_celsius", "Temperature reported by an EnOcean sensor, in °C", temperature.clone());
+        registry.register("enocean_humidity_percent", "Relative humidity reported by an EnOcean sensor, in %", humidity.clone());
+        registry.register("enocean_last_seen_timestamp_seconds", "Unix timestamp of the last telegram received from a device", last_seen.clone());
+
+        let up = Family::<DeviceLabels, Gauge<i64, std::sync::atomic::AtomicI64>>::default();
+        let last_seen_seconds = Family::<DeviceLabels, Gauge<f64, AtomicU64>>::default();
+        registry.register("enocean_up", "1 if the device has a reading within max_age, 0 if stale or never seen", up.clone());
+        registry.register("enocean_last_seen_seconds", "Seconds since the last telegram was received from this device", last_seen_seconds.clone());
+
+        let devices_known = Gauge::<i64, std::sync::atomic::AtomicI64>::default();
+        let devices_reporting = Gauge::<i64, std::sync::atomic::AtomicI64>::default();
+        registry.register("enocean_devices_known", "Number of devices configured or discovered", devices_known.clone());
+        registry.register("enocean_devices_reporting", "Number of devices with a fresh reading", devices_reporting.clone());
+        registry.register(
+            "enocean_build",
+            "Build information for this exporter",
+            Info::new(vec![("version".to_string(), env!("CARGO_PKG_VERSION").to_string())]),
+        );
+
+        let now = SystemTime::now();
+        devices_known.set(self.devices.len() as i64);
+        let mut reporting = 0i64;
+
+        for (address, device) in self.devices.iter() {
+            let labels = DeviceLabels {
+                address: address.to_string(),
+                name: device.name.clone().unwrap_or_default(),
+            };
+
+            let last_reading = device.readings.values().map(|(_, time)| *time).max();
+            let fresh = last_reading.is_some_and(|time| self.is_fresh(now, time));
+
+            up.get_or_create(&labels).set(fresh as i64);
+            if let Some(time) = last_reading {
+                let age = now.duration_since(time).unwrap_or_default().as_secs_f64();
+                last_seen_seconds.get_or_create(&labels).set(age);
+            }
+
+            if !fresh {
+                continue;
             }
+            reporting += 1;
+
+            if let Some((value, time)) = device.readings.get("temperature") {
+                temperature.get_or_create(&labels).set(*value);
+                last_seen.get_or_create(&labels).set(unix_seconds(*time));
+            }
+            if let Some((value, time)) = device.readings.get("humidity") {
+                humidity.get_or_create(&labels).set(*value);
+                last_seen.get_or_create(&labels).set(unix_seconds(*time));
+            }
+        }
+        devices_reporting.set(reporting);
+
+        let setpoint = Family::<DeviceLabels, Gauge<f64, AtomicU64>>::default();
+        let actuator_on = Family::<DeviceLabels, Gauge<i64, std::sync::atomic::AtomicI64>>::default();
+        let duty_cycle = Family::<DeviceLabels, Gauge<f64, AtomicU64>>::default();
+        registry.register("enocean_thermostat_setpoint_celsius", "Configured thermostat target temperature", setpoint.clone());
+        registry.register("enocean_thermostat_actuator_on", "1 if the thermostat's actuator is currently switched on", actuator_on.clone());
+        registry.register("enocean_thermostat_duty_cycle_ratio", "Fraction of time the actuator has spent on", duty_cycle.clone());
+
+        for (sensor, thermostat) in self.thermostats.iter() {
+            let labels = DeviceLabels {
+                address: sensor.to_string(),
+                name: self.devices.get(sensor).and_then(|d| d.name.clone()).unwrap_or_default(),
+            };
+            setpoint.get_or_create(&labels).set(thermostat.setpoint());
+            actuator_on.get_or_create(&labels).set((thermostat.state() == ActuatorState::On) as i64);
+            duty_cycle.get_or_create(&labels).set(thermostat.duty_cycle(Instant::now()));
         }
 
-        scrape
+        let mut buffer = String::new();
+        encode(&mut buffer, &registry).expect("encoding metrics should not fail");
+        buffer
     }
 
 }
 
+fn unix_seconds(time: Timestamp) -> f64 {
+    time.duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs_f64()
+}
+
+/// Parses one entry of the YAML `thermostats` table into the sensor
+/// address it watches and its controller config. Shared by the
+/// startup parse (`with_thermostats`) and the reload merge
+/// (`merge_thermostats`) so the two can't drift.
+fn parse_thermostat(sensor: &Yaml, fields: &Yaml) -> Result<(Address, ThermostatConfig)> {
+    let sensor: Address = sensor.as_str().ok_or("thermostat sensor address was not a string")?.parse()?;
+    let fields = fields.as_hash().ok_or("thermostat entry was not a table")?;
+
+    let actuator: Address = fields.get(&Yaml::String("actuator".into()))
+        .and_then(Yaml::as_str).ok_or("thermostat actuator address missing")?
+        .parse()?;
+    let setpoint = fields.get(&Yaml::String("setpoint".into()))
+        .and_then(Yaml::as_f64).ok_or("thermostat setpoint missing")?;
+    let hysteresis = fields.get(&Yaml::String("hysteresis".into()))
+        .and_then(Yaml::as_f64).ok_or("thermostat hysteresis missing")?;
+    let min_dwell = fields.get(&Yaml::String("min_dwell_seconds".into()))
+        .and_then(Yaml::as_i64).map(|secs| Duration::from_secs(secs as u64))
+        .unwrap_or(Duration::from_secs(300));
+
+    Ok((sensor, ThermostatConfig { actuator, setpoint, hysteresis, min_dwell }))
+}
+
 #[tokio::main(flavor="current_thread")]
 async fn main() -> Result<()> {
     let mut config_file = String::new();
@@ -70,53 +314,206 @@ async fn main() -> Result<()> {
     let port_name = config["port"].as_str().ok_or("port name not found in config")?;
     let listen: SocketAddr = config["listen"].as_str().ok_or("listen was not a string")?.parse()?;
     let devices = config["devices"].as_hash().ok_or("devices is not a table")?;
+    let max_age = config["max_age"].as_i64().map(|secs| Duration::from_secs(secs as u64));
+
+    let (cmd_tx, cmd_rx) = mpsc::channel();
 
-    let store = TemperatureStore::with_devices(devices)?;
+    let store = TemperatureStore::with_devices(devices, max_age)?;
+    let store = match config["thermostats"].as_hash() {
+        Some(thermostats) => store.with_thermostats(thermostats, cmd_tx)?,
+        None => store,
+    };
     let store = Arc::new(Mutex::new(store));
-    
-    let port = Port::open(port_name)?;
-    eprintln!("Port {port_name} opened.");
+
+    let shutdown = Arc::new(AtomicBool::new(false));
 
     let driver_store = store.clone();
-    Handle::current().spawn_blocking(move || { serial_driver_thread(port, driver_store)} );
+    let driver_port_name = port_name.to_owned();
+    let driver_shutdown = shutdown.clone();
+    Handle::current().spawn_blocking(move || { serial_driver_thread(driver_port_name, driver_store, driver_shutdown, cmd_rx) });
 
     let home = format!("<html><body><h1>EnOcean Temperature exporter</h1><ul><li>port {port_name}</li><li><a href=\"/metrics\">metrics</a></li></ul></body></html>");
     let home: &'static str = Box::leak(home.into_boxed_str());
 
-    let filter = warp::path!("metrics").map(move || store.lock().unwrap().scrape())
-             .or(warp::path!().map(move || { warp::reply::html(home) }));
+    let api_store = store.clone();
+    let filter = warp::path!("metrics")
+             .map(move || {
+                 let body = store.lock().unwrap().scrape();
+                 warp::reply::with_header(body, "Content-Type", "application/openmetrics-text; version=1.0.0; charset=utf-8")
+             })
+             .or(warp::path!().map(move || { warp::reply::html(home) }))
+             .or(api::routes(api_store, "temperature_exporter.yaml"));
 
-    Ok(warp::serve(filter).run(listen).await)
+    let (_, server) = warp::serve(filter).bind_with_graceful_shutdown(listen, async move {
+        wait_for_shutdown_signal().await;
+        eprintln!("Shutdown signal received, draining connections.");
+    });
+    server.await;
 
-    /*
-    let mut tick = String::new();
-    loop {
-        std::io::stdin().read_line(&mut tick)?;
-        println!("{}", store.lock().unwrap().scrape());
-    }
-    */
+    shutdown.store(true, Ordering::Relaxed);
+    eprintln!("Stopping serial driver thread.");
 
+    Ok(())
 }
 
-fn serial_driver_thread(mut port: Port, store: Arc<Mutex<TemperatureStore>>) {
-    loop {
-        let Ok(frame) = port.read_frame() else { continue };
-        eprintln!("Frame: {frame:?}");
+/// Resolves once the process receives SIGINT or SIGTERM.
+async fn wait_for_shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
 
-        let Ok(pkt) = Packet::decode(frame.as_ref())
-            .map_err(|e| eprintln!("Cannot decode: {e}")) else { continue };
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = sigterm.recv() => {},
+    }
+}
+
+/// Initial backoff before the first reconnect attempt.
+const RECONNECT_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+/// Backoff cap so a long outage still retries roughly once a minute.
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// How often the connection loop wakes up to recheck `shutdown` even
+/// when no frame has arrived.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Reader threads from past connections that are still blocked in
+/// `read_frame()` (rather than having exited on error or disconnect) are
+/// leaked on reconnect. That's tolerable if it's rare, but if it keeps
+/// happening it means reads are wedging instead of erroring, and piling
+/// up abandoned threads forever is worse than giving up. Bail out of the
+/// driver thread entirely once this many are stuck at once, so the
+/// failure is visible instead of silently consuming resources.
+const MAX_STUCK_READER_THREADS: usize = 8;
+
+fn serial_driver_thread(port_name: String, store: Arc<Mutex<TemperatureStore>>, shutdown: Arc<AtomicBool>, cmd_rx: mpsc::Receiver<Packet>) {
+    let mut backoff = RECONNECT_BACKOFF_INITIAL;
+    let stuck_reader_threads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    while !shutdown.load(Ordering::Relaxed) {
+        if stuck_reader_threads.load(Ordering::Relaxed) >= MAX_STUCK_READER_THREADS {
+            eprintln!("{port_name}: {MAX_STUCK_READER_THREADS} reader threads are stuck blocked on read_frame(), giving up");
+            break;
+        }
+
+        let mut port = match Port::open(&port_name) {
+            Ok(port) => port,
+            Err(e) => {
+                eprintln!("Cannot open {port_name}: {e}, retrying in {backoff:?}");
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                continue;
+            }
+        };
+        eprintln!("Port {port_name} opened.");
+
+        // Outbound telegrams must carry the dongle's own base ID as
+        // sender_id, not the address of whatever sensor we're reacting
+        // to. Read it once per connection; if the dongle won't answer,
+        // fall back to the zero address rather than failing the whole
+        // connection, since the driver can still read and this merely
+        // degrades actuator commands.
+        let base_id = match port.base_id() {
+            Ok(base_id) => base_id,
+            Err(e) => {
+                eprintln!("Cannot read base ID from {port_name}: {e}, actuator commands will use the zero address");
+                Address::default()
+            }
+        };
+
+        // `port.read_frame()` blocks indefinitely while the sensor network
+        // is quiet, which would also block this thread from ever noticing
+        // `shutdown`. Run it on its own thread instead, fed back through a
+        // channel the connection loop polls with a timeout; that keeps
+        // `shutdown` responsive no matter how long reads take. Writes use
+        // a cloned handle so commands can still go out independently. This
+        // reader thread is never joined; on a normal reconnect it exits on
+        // its own once `read_frame` errors or `frame_rx` is dropped, but if
+        // a particular failure wedges it forever instead, it's counted in
+        // `stuck_reader_threads` above rather than leaking unbounded.
+        let mut write_port = match port.try_clone() {
+            Ok(write_port) => Some(write_port),
+            Err(e) => {
+                eprintln!("Cannot clone {port_name} for writes: {e}, actuator commands disabled until reconnect");
+                None
+            }
+        };
 
-        if let Packet::RadioErp1(erp) = pkt {
-            if erp.choice == Rorg::Bs4 {
-                let temperature = decode_temperature(erp.user_data[2]);
-                let address = erp.sender_id;
-                let timestamp = SystemTime::now();
-                store.lock().unwrap().insert(address, temperature, timestamp);
+        let (frame_tx, frame_rx) = mpsc::channel();
+        let mut read_port = port;
+        stuck_reader_threads.fetch_add(1, Ordering::Relaxed);
+        let reader_exited = stuck_reader_threads.clone();
+        std::thread::spawn(move || {
+            loop {
+                let result = read_port.read_frame();
+                let failed = result.is_err();
+                if frame_tx.send(result).is_err() || failed {
+                    break;
+                }
+            }
+            // Only reached once read_frame actually returns, with an error
+            // or after the connection loop has stopped listening; a thread
+            // permanently blocked in read_frame never gets here, which is exactly
+            // the condition MAX_STUCK_READER_THREADS is meant to catch.
+            reader_exited.fetch_sub(1, Ordering::Relaxed);
+        });
+
+        'connection: while !shutdown.load(Ordering::Relaxed) {
+            if let Some(write_port) = write_port.as_mut() {
+                while let Ok(cmd) = cmd_rx.try_recv() {
+                    if let Err(e) = send_command(write_port, base_id, &cmd) {
+                        eprintln!("Failed to send actuator command on {port_name}: {e}");
+                    }
+                }
+            }
+
+            let frame = match frame_rx.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+                Ok(Ok(frame)) => {
+                    backoff = RECONNECT_BACKOFF_INITIAL;
+                    frame
+                }
+                Ok(Err(e)) => {
+                    eprintln!("Read error on {port_name}: {e}, reconnecting in {backoff:?}");
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                    break 'connection;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => continue 'connection,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break 'connection,
+            };
+            eprintln!("Frame: {frame:?}");
+
+            let Ok(pkt) = Packet::decode(frame.as_ref())
+                .map_err(|e| eprintln!("Cannot decode: {e}")) else { continue };
+
+            if let Packet::RadioErp1(erp) = pkt {
+                if erp.choice == Rorg::Bs4 {
+                    let address = erp.sender_id;
+                    let mut store = store.lock().unwrap();
+                    let Some(eep) = store.devices.get(&address).and_then(|d| d.eep) else {
+                        eprintln!("Device {address}: no EEP configured, skipping telegram");
+                        continue;
+                    };
+                    match eep::decode(eep, &erp.user_data) {
+                        Some(measurements) => store.insert(address, measurements, SystemTime::now()),
+                        None => eprintln!("Device {address}: telegram doesn't match configured EEP {eep:?}, skipping"),
+                    }
+                }
             }
         }
+        // write_port (and the reader thread's read_port, once it notices)
+        // are dropped here on the way back to the outer loop, so the next
+        // Port::open starts from a clean slate.
     }
+
+    eprintln!("Serial driver thread for {port_name} stopped.");
 }
 
-fn decode_temperature(byte: u8) -> f64 {
-    40f64 - (byte as f64 * 80f64 / 255f64).round() / 2f64
+/// Stamps `base_id` as the sender before writing, since `actuator_command`
+/// doesn't know the dongle's own address (only the driver thread, which
+/// owns the `Port`, does).
+fn send_command(port: &mut Port, base_id: Address, packet: &Packet) -> std::io::Result<()> {
+    let mut packet = packet.clone();
+    if let Packet::RadioErp1(erp) = &mut packet {
+        erp.sender_id = base_id;
+    }
+    port.write_all(&packet.encode())
 }
\ No newline at end of file